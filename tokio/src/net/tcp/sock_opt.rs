@@ -5,13 +5,32 @@ mod windows;
 use cfg_if::cfg_if;
 use std::io;
 use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::ops::Neg;
 use std::time::Duration;
 
-#[cfg(any(unix, target_os = "redox"))]
-use libc::*;
+// Hermit has no `libc` socket support of its own, so its libc-shaped
+// `setsockopt`/`getsockopt` surface lives in `hermit_abi::netc` instead -
+// everywhere else (including WASI, which `libc` supports for the subset of
+// options it exposes) pulls the real thing straight from `libc`. Pulling
+// this in requires a `[target.'cfg(target_os = "hermit")'.dependencies]
+// hermit_abi = "..."` entry in `tokio/Cargo.toml`; on hermit only buffers,
+// keepalive, linger and `peek` are wired up, so the option blocks left out
+// (multicast, nodelay, reuse_address, ttl, broadcast, recv_oob/OOB-inline)
+// exclude `target_os = "hermit"` the same way they exclude `redox`.
+cfg_if! {
+    if #[cfg(target_os = "hermit")] {
+        use hermit_abi::netc::*;
+    } else if #[cfg(any(unix, target_os = "redox", target_os = "wasi"))] {
+        use libc::*;
+    }
+}
 #[cfg(any(unix, target_os = "redox"))]
 use std::os::unix::prelude::*;
+#[cfg(target_os = "wasi")]
+use std::os::wasi::prelude::*;
+#[cfg(target_os = "hermit")]
+use std::os::hermit::io::*;
 #[cfg(windows)]
 use std::os::windows::prelude::*;
 
@@ -20,7 +39,7 @@ use windows::*;
 
 #[cfg(target_os = "redox")]
 type Socket = usize;
-#[cfg(unix)]
+#[cfg(any(unix, target_os = "wasi", target_os = "hermit"))]
 type Socket = c_int;
 #[cfg(windows)]
 type Socket = SOCKET;
@@ -35,7 +54,69 @@ struct tcp_keepalive {
     keepaliveinterval: c_ulong,
 }
 
-#[cfg(any(unix, target_os = "redox"))]
+// Windows-side constants analogous to `SIO_KEEPALIVE_VALS` above: `libc`
+// only defines these for unix, so mirror the Winsock values from
+// `ws2ipdef.h` (and `winsock2.h` for the `MSG_*`/`SO_OOBINLINE` ones) here.
+#[cfg(windows)]
+const IPPROTO_IP: c_int = 0;
+#[cfg(windows)]
+const IPPROTO_IPV6: c_int = 41;
+#[cfg(windows)]
+const IP_ADD_MEMBERSHIP: c_int = 12;
+#[cfg(windows)]
+const IP_DROP_MEMBERSHIP: c_int = 13;
+#[cfg(windows)]
+const IP_TTL: c_int = 4;
+#[cfg(windows)]
+const IP_MULTICAST_TTL: c_int = 10;
+#[cfg(windows)]
+const IP_MULTICAST_LOOP: c_int = 11;
+#[cfg(windows)]
+const IPV6_ADD_MEMBERSHIP: c_int = 12;
+#[cfg(windows)]
+const IPV6_DROP_MEMBERSHIP: c_int = 13;
+#[cfg(windows)]
+const IPV6_MULTICAST_HOPS: c_int = 10;
+#[cfg(windows)]
+const IPV6_MULTICAST_LOOP: c_int = 11;
+#[cfg(windows)]
+const MSG_OOB: c_int = 0x1;
+#[cfg(windows)]
+const MSG_PEEK: c_int = 0x2;
+#[cfg(windows)]
+const SO_OOBINLINE: c_int = 0x0100;
+
+#[cfg(windows)]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct in_addr {
+    s_addr: u32,
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct in6_addr {
+    s6_addr: [u8; 16],
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ip_mreq {
+    imr_multiaddr: in_addr,
+    imr_interface: in_addr,
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ipv6_mreq {
+    ipv6mr_multiaddr: in6_addr,
+    ipv6mr_interface: c_ulong,
+}
+
+#[cfg(any(unix, target_os = "redox", target_os = "hermit"))]
 fn v(opt: c_int) -> c_int {
     opt
 }
@@ -78,11 +159,28 @@ fn get_opt<T: Copy>(sock: Socket, opt: c_int, val: c_int) -> io::Result<T> {
     }
 }
 
+/// Converts between the platform's socket-option integer widths without
+/// silently truncating - `InvalidInput` beats a value that means something
+/// else entirely on one platform's ABI.
+fn checked_cast<T, U: TryFrom<T>>(val: T) -> io::Result<U> {
+    U::try_from(val)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "value out of range"))
+}
+
+fn do_recv(sock: Socket, buf: &mut [u8], flags: c_int) -> io::Result<usize> {
+    unsafe {
+        #[cfg(target_os = "redox")]
+        let sock = sock as c_int;
+        let n = cvt(recv(sock, buf.as_mut_ptr() as *mut _, buf.len() as _, flags))?;
+        Ok(n as usize)
+    }
+}
+
 pub(crate) trait AsSock {
     fn as_sock(&self) -> Socket;
 }
 
-#[cfg(any(unix, target_os = "redox"))]
+#[cfg(any(unix, target_os = "redox", target_os = "wasi", target_os = "hermit"))]
 impl<T: AsRawFd> AsSock for T {
     fn as_sock(&self) -> Socket {
         self.as_raw_fd()
@@ -95,6 +193,30 @@ impl<T: AsRawSocket> AsSock for T {
     }
 }
 
+// `IP_MULTICAST_TTL`/`IP_MULTICAST_LOOP` take a 4-byte `c_int` on Linux and
+// Windows, but a 1-byte `u_char` on the BSDs (including macOS/iOS) - sending
+// the wrong width there gets rejected with `EINVAL`. socket2 special-cases
+// the same set of platforms for the same reason.
+cfg_if! {
+    if #[cfg(any(
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "ios",
+        target_os = "macos",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "tvos",
+        target_os = "visionos",
+        target_os = "watchos",
+    ))] {
+        type IpMulticastTtl = c_uchar;
+        type IpMulticastLoop = c_uchar;
+    } else {
+        type IpMulticastTtl = c_int;
+        type IpMulticastLoop = c_int;
+    }
+}
+
 cfg_if! {
     if #[cfg(any(target_os = "macos", target_os = "ios"))] {
         use libc::TCP_KEEPALIVE as KEEPALIVE_OPTION;
@@ -102,11 +224,88 @@ cfg_if! {
         use libc::SO_KEEPALIVE as KEEPALIVE_OPTION;
     } else if #[cfg(any(unix, target_os = "redox"))] {
         use libc::TCP_KEEPIDLE as KEEPALIVE_OPTION;
+    } else if #[cfg(target_os = "hermit")] {
+        use hermit_abi::netc::TCP_KEEPIDLE as KEEPALIVE_OPTION;
     } else {
         // ...
     }
 }
 
+cfg_if! {
+    if #[cfg(any(
+        target_os = "android",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "illumos",
+        target_os = "ios",
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "netbsd",
+        target_os = "openbsd",
+    ))] {
+        fn set_reuse_port_opt<T: AsSock>(this: &T, reuse: bool) -> io::Result<()> {
+            set_opt(this.as_sock(), SOL_SOCKET, SO_REUSEPORT, reuse as c_int)
+        }
+
+        fn reuse_port_opt<T: AsSock>(this: &T) -> io::Result<bool> {
+            get_opt::<c_int>(this.as_sock(), SOL_SOCKET, SO_REUSEPORT).map(|v| v != 0)
+        }
+    } else {
+        // `SO_REUSEPORT` doesn't exist on Windows (or the remaining unix
+        // targets) - there's no equivalent socket option to emulate it with,
+        // so report it as unsupported rather than silently doing nothing.
+        fn set_reuse_port_opt<T: AsSock>(_this: &T, _reuse: bool) -> io::Result<()> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+
+        fn reuse_port_opt<T: AsSock>(_this: &T) -> io::Result<bool> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+}
+
+/// Configuration for a socket's `TCP_KEEPALIVE` probes, with the idle time,
+/// probe interval, and probe count tracked independently.
+///
+/// Build one with [`TcpKeepalive::new`] and the `with_*` setters, then pass
+/// it to [`SockOpt::set_tcp_keepalive`].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TcpKeepalive {
+    idle: Option<Duration>,
+    interval: Option<Duration>,
+    retries: Option<u32>,
+}
+
+impl TcpKeepalive {
+    pub(crate) fn new() -> TcpKeepalive {
+        TcpKeepalive {
+            idle: None,
+            interval: None,
+            retries: None,
+        }
+    }
+
+    /// Sets the amount of time after which TCP keepalive probes will be sent
+    /// on an idle connection.
+    pub(crate) fn with_idle(mut self, idle: Duration) -> Self {
+        self.idle = Some(idle);
+        self
+    }
+
+    /// Sets the time interval between TCP keepalive probes.
+    pub(crate) fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Sets the maximum number of TCP keepalive probes that will be sent
+    /// before dropping a connection.
+    pub(crate) fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+}
+
 pub(crate) trait SockOpt {
     fn set_recv_buffer_size(&self, size: usize) -> io::Result<()>;
 
@@ -124,57 +323,132 @@ pub(crate) trait SockOpt {
 
     fn keepalive_ms(&self) -> io::Result<Option<u32>>;
 
+    /// Sets the idle time, probe interval, and probe count of a socket's
+    /// TCP keepalive, independently of one another.
+    ///
+    /// The probe interval and count are applied only on Linux, Android,
+    /// macOS and iOS; on other unix targets and hermit only the idle time is
+    /// set. Windows applies the idle time and interval and ignores the count.
+    fn set_tcp_keepalive(&self, keepalive: &TcpKeepalive) -> io::Result<()>;
+
     fn set_linger(&self, dur: Option<Duration>) -> io::Result<()>;
 
     fn linger(&self) -> io::Result<Option<Duration>>;
+
+    fn join_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()>;
+
+    fn leave_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()>;
+
+    fn join_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()>;
+
+    fn leave_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()>;
+
+    fn set_multicast_loop_v4(&self, on: bool) -> io::Result<()>;
+
+    fn multicast_loop_v4(&self) -> io::Result<bool>;
+
+    fn set_multicast_loop_v6(&self, on: bool) -> io::Result<()>;
+
+    fn multicast_loop_v6(&self) -> io::Result<bool>;
+
+    fn set_multicast_ttl_v4(&self, ttl: u32) -> io::Result<()>;
+
+    fn multicast_ttl_v4(&self) -> io::Result<u32>;
+
+    fn set_multicast_hops_v6(&self, hops: u32) -> io::Result<()>;
+
+    fn multicast_hops_v6(&self) -> io::Result<u32>;
+
+    fn set_nodelay(&self, nodelay: bool) -> io::Result<()>;
+
+    fn nodelay(&self) -> io::Result<bool>;
+
+    fn set_reuse_address(&self, reuse: bool) -> io::Result<()>;
+
+    fn reuse_address(&self) -> io::Result<bool>;
+
+    /// Not supported on platforms without `SO_REUSEPORT` (notably Windows),
+    /// where this returns an `Unsupported` error.
+    fn set_reuse_port(&self, reuse: bool) -> io::Result<()>;
+
+    fn reuse_port(&self) -> io::Result<bool>;
+
+    fn set_ttl(&self, ttl: u32) -> io::Result<()>;
+
+    fn ttl(&self) -> io::Result<u32>;
+
+    fn set_broadcast(&self, broadcast: bool) -> io::Result<()>;
+
+    fn broadcast(&self) -> io::Result<bool>;
+
+    /// Reads data from the socket without consuming it - a subsequent read
+    /// will return the same bytes. Implemented with `MSG_PEEK`.
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Receives out-of-band ("urgent") data. Implemented with `MSG_OOB`.
+    fn recv_oob(&self, buf: &mut [u8]) -> io::Result<usize>;
+
+    fn set_out_of_band_inline(&self, oobinline: bool) -> io::Result<()>;
+
+    fn out_of_band_inline(&self) -> io::Result<bool>;
 }
 
 impl<T: AsSock> SockOpt for T {
     fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
-        // TODO: casting usize to a c_int should be a checked cast
-        set_opt(self.as_sock(), SOL_SOCKET, SO_RCVBUF, size as c_int)
+        set_opt(
+            self.as_sock(),
+            SOL_SOCKET,
+            SO_RCVBUF,
+            checked_cast::<_, c_int>(size)?,
+        )
     }
 
     fn recv_buffer_size(&self) -> io::Result<usize> {
-        get_opt(self.as_sock(), SOL_SOCKET, SO_RCVBUF).map(int2usize)
+        get_opt(self.as_sock(), SOL_SOCKET, SO_RCVBUF).and_then(int2usize)
     }
 
     fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
-        set_opt(self.as_sock(), SOL_SOCKET, SO_SNDBUF, size as c_int)
+        set_opt(
+            self.as_sock(),
+            SOL_SOCKET,
+            SO_SNDBUF,
+            checked_cast::<_, c_int>(size)?,
+        )
     }
 
     fn send_buffer_size(&self) -> io::Result<usize> {
-        get_opt(self.as_sock(), SOL_SOCKET, SO_SNDBUF).map(int2usize)
+        get_opt(self.as_sock(), SOL_SOCKET, SO_SNDBUF).and_then(int2usize)
     }
 
     fn set_keepalive(&self, keepalive: Option<Duration>) -> io::Result<()> {
-        self.set_keepalive_ms(keepalive.map(dur2ms))
+        self.set_keepalive_ms(keepalive.map(dur2ms).transpose()?)
     }
 
     fn keepalive(&self) -> io::Result<Option<Duration>> {
         self.keepalive_ms().map(|o| o.map(ms2dur))
     }
 
-    #[cfg(any(unix, target_os = "redox"))]
+    #[cfg(any(unix, target_os = "redox", target_os = "hermit"))]
     fn set_keepalive_ms(&self, keepalive: Option<u32>) -> io::Result<()> {
+        // Thin wrapper over `set_tcp_keepalive` that only touches the idle
+        // time, kept for backward compatibility.
+        let ka = match keepalive {
+            Some(ms) => TcpKeepalive::new().with_idle(Duration::from_millis(ms as u64)),
+            None => TcpKeepalive::new(),
+        };
         set_opt(
             self.as_sock(),
             SOL_SOCKET,
             SO_KEEPALIVE,
             keepalive.is_some() as c_int,
         )?;
-        if let Some(dur) = keepalive {
-            set_opt(
-                self.as_sock(),
-                v(IPPROTO_TCP),
-                KEEPALIVE_OPTION,
-                (dur / 1000) as c_int,
-            )?;
+        if keepalive.is_some() {
+            self.set_tcp_keepalive(&ka)?;
         }
         Ok(())
     }
 
-    #[cfg(any(unix, target_os = "redox"))]
+    #[cfg(any(unix, target_os = "redox", target_os = "hermit"))]
     fn keepalive_ms(&self) -> io::Result<Option<u32>> {
         let keepalive = get_opt::<c_int>(self.as_sock(), SOL_SOCKET, SO_KEEPALIVE)?;
         if keepalive == 0 {
@@ -184,28 +458,53 @@ impl<T: AsSock> SockOpt for T {
         Ok(Some((secs as u32) * 1000))
     }
 
+    #[cfg(any(unix, target_os = "redox", target_os = "hermit"))]
+    fn set_tcp_keepalive(&self, keepalive: &TcpKeepalive) -> io::Result<()> {
+        set_opt(self.as_sock(), SOL_SOCKET, SO_KEEPALIVE, true as c_int)?;
+        if let Some(idle) = keepalive.idle {
+            set_opt(
+                self.as_sock(),
+                v(IPPROTO_TCP),
+                KEEPALIVE_OPTION,
+                checked_cast::<_, c_int>(idle.as_secs())?,
+            )?;
+        }
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios"
+        ))]
+        {
+            if let Some(interval) = keepalive.interval {
+                set_opt(
+                    self.as_sock(),
+                    v(IPPROTO_TCP),
+                    TCP_KEEPINTVL,
+                    checked_cast::<_, c_int>(interval.as_secs())?,
+                )?;
+            }
+            if let Some(retries) = keepalive.retries {
+                set_opt(
+                    self.as_sock(),
+                    v(IPPROTO_TCP),
+                    TCP_KEEPCNT,
+                    checked_cast::<_, c_int>(retries)?,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     #[cfg(windows)]
     fn set_keepalive_ms(&self, keepalive: Option<u32>) -> io::Result<()> {
-        let ms = keepalive.unwrap_or(INFINITE);
-        let ka = tcp_keepalive {
-            onoff: keepalive.is_some() as c_ulong,
-            keepalivetime: ms as c_ulong,
-            keepaliveinterval: ms as c_ulong,
+        // Thin wrapper over `set_tcp_keepalive` that only touches the idle
+        // time, kept for backward compatibility.
+        let ka = match keepalive {
+            Some(ms) => TcpKeepalive::new().with_idle(Duration::from_millis(ms as u64)),
+            None => TcpKeepalive::new(),
         };
-        unsafe {
-            cvt_win(WSAIoctl(
-                self.as_sock(),
-                SIO_KEEPALIVE_VALS,
-                &ka as *const _ as *mut _,
-                mem::size_of_val(&ka) as DWORD,
-                0 as *mut _,
-                0,
-                0 as *mut _,
-                0 as *mut _,
-                None,
-            ))
-            .map(|_| ())
-        }
+        self.set_tcp_keepalive(&ka)
     }
 
     #[cfg(windows)]
@@ -237,13 +536,364 @@ impl<T: AsSock> SockOpt for T {
         })
     }
 
+    // Windows has no separate probe-count knob, so `retries` is ignored.
+    #[cfg(windows)]
+    fn set_tcp_keepalive(&self, keepalive: &TcpKeepalive) -> io::Result<()> {
+        let idle_ms = match keepalive.idle {
+            Some(d) => checked_cast(d.as_millis())?,
+            None => INFINITE as c_ulong,
+        };
+        let interval_ms = match keepalive.interval {
+            Some(d) => checked_cast(d.as_millis())?,
+            None => idle_ms,
+        };
+        let ka = tcp_keepalive {
+            onoff: (keepalive.idle.is_some() || keepalive.interval.is_some()) as c_ulong,
+            keepalivetime: idle_ms,
+            keepaliveinterval: interval_ms,
+        };
+        unsafe {
+            cvt_win(WSAIoctl(
+                self.as_sock(),
+                SIO_KEEPALIVE_VALS,
+                &ka as *const _ as *mut _,
+                mem::size_of_val(&ka) as DWORD,
+                0 as *mut _,
+                0,
+                0 as *mut _,
+                0 as *mut _,
+                None,
+            ))
+            .map(|_| ())
+        }
+    }
+
+    #[cfg(target_os = "wasi")]
+    fn set_keepalive_ms(&self, _keepalive: Option<u32>) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(target_os = "wasi")]
+    fn keepalive_ms(&self) -> io::Result<Option<u32>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(target_os = "wasi")]
+    fn set_tcp_keepalive(&self, _keepalive: &TcpKeepalive) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(not(target_os = "wasi"))]
     fn set_linger(&self, dur: Option<Duration>) -> io::Result<()> {
-        set_opt(self.as_sock(), SOL_SOCKET, SO_LINGER, dur2linger(dur))
+        set_opt(self.as_sock(), SOL_SOCKET, SO_LINGER, dur2linger(dur)?)
     }
 
+    #[cfg(not(target_os = "wasi"))]
     fn linger(&self) -> io::Result<Option<Duration>> {
         get_opt(self.as_sock(), SOL_SOCKET, SO_LINGER).map(linger2dur)
     }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn join_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        let mreq = ip_mreq {
+            imr_multiaddr: ipv4addr2in_addr(multiaddr),
+            imr_interface: ipv4addr2in_addr(interface),
+        };
+        set_opt(self.as_sock(), IPPROTO_IP, IP_ADD_MEMBERSHIP, mreq)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn leave_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        let mreq = ip_mreq {
+            imr_multiaddr: ipv4addr2in_addr(multiaddr),
+            imr_interface: ipv4addr2in_addr(interface),
+        };
+        set_opt(self.as_sock(), IPPROTO_IP, IP_DROP_MEMBERSHIP, mreq)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn join_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        let mreq = ipv6_mreq {
+            ipv6mr_multiaddr: ipv6addr2in6_addr(multiaddr),
+            ipv6mr_interface: interface as _,
+        };
+        set_opt(self.as_sock(), IPPROTO_IPV6, IPV6_ADD_MEMBERSHIP, mreq)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn leave_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        let mreq = ipv6_mreq {
+            ipv6mr_multiaddr: ipv6addr2in6_addr(multiaddr),
+            ipv6mr_interface: interface as _,
+        };
+        set_opt(self.as_sock(), IPPROTO_IPV6, IPV6_DROP_MEMBERSHIP, mreq)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn set_multicast_loop_v4(&self, on: bool) -> io::Result<()> {
+        set_opt(
+            self.as_sock(),
+            IPPROTO_IP,
+            IP_MULTICAST_LOOP,
+            on as IpMulticastLoop,
+        )
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn multicast_loop_v4(&self) -> io::Result<bool> {
+        get_opt::<IpMulticastLoop>(self.as_sock(), IPPROTO_IP, IP_MULTICAST_LOOP).map(|v| v != 0)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn set_multicast_loop_v6(&self, on: bool) -> io::Result<()> {
+        set_opt(self.as_sock(), IPPROTO_IPV6, IPV6_MULTICAST_LOOP, on as c_int)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn multicast_loop_v6(&self) -> io::Result<bool> {
+        get_opt::<c_int>(self.as_sock(), IPPROTO_IPV6, IPV6_MULTICAST_LOOP).map(|v| v != 0)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn set_multicast_ttl_v4(&self, ttl: u32) -> io::Result<()> {
+        set_opt(
+            self.as_sock(),
+            IPPROTO_IP,
+            IP_MULTICAST_TTL,
+            checked_cast::<_, IpMulticastTtl>(ttl)?,
+        )
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn multicast_ttl_v4(&self) -> io::Result<u32> {
+        get_opt::<IpMulticastTtl>(self.as_sock(), IPPROTO_IP, IP_MULTICAST_TTL).map(|v| v as u32)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn set_multicast_hops_v6(&self, hops: u32) -> io::Result<()> {
+        set_opt(self.as_sock(), IPPROTO_IPV6, IPV6_MULTICAST_HOPS, hops as c_int)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn multicast_hops_v6(&self) -> io::Result<u32> {
+        get_opt::<c_int>(self.as_sock(), IPPROTO_IPV6, IPV6_MULTICAST_HOPS).map(|v| v as u32)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        set_opt(self.as_sock(), v(IPPROTO_TCP), TCP_NODELAY, nodelay as c_int)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn nodelay(&self) -> io::Result<bool> {
+        get_opt::<c_int>(self.as_sock(), v(IPPROTO_TCP), TCP_NODELAY).map(|v| v != 0)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn set_reuse_address(&self, reuse: bool) -> io::Result<()> {
+        set_opt(self.as_sock(), SOL_SOCKET, SO_REUSEADDR, reuse as c_int)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn reuse_address(&self) -> io::Result<bool> {
+        get_opt::<c_int>(self.as_sock(), SOL_SOCKET, SO_REUSEADDR).map(|v| v != 0)
+    }
+
+    fn set_reuse_port(&self, reuse: bool) -> io::Result<()> {
+        set_reuse_port_opt(self, reuse)
+    }
+
+    fn reuse_port(&self) -> io::Result<bool> {
+        reuse_port_opt(self)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        set_opt(self.as_sock(), IPPROTO_IP, IP_TTL, ttl as c_int)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn ttl(&self) -> io::Result<u32> {
+        get_opt::<c_int>(self.as_sock(), IPPROTO_IP, IP_TTL).map(|v| v as u32)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn set_broadcast(&self, broadcast: bool) -> io::Result<()> {
+        set_opt(self.as_sock(), SOL_SOCKET, SO_BROADCAST, broadcast as c_int)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn broadcast(&self) -> io::Result<bool> {
+        get_opt::<c_int>(self.as_sock(), SOL_SOCKET, SO_BROADCAST).map(|v| v != 0)
+    }
+
+    // WASI preview1 sockets only support the buffer-size options above;
+    // linger, keepalive (handled by `set_keepalive_ms`/`keepalive_ms`
+    // above), multicast, and the remaining toggles have no equivalent in
+    // the ABI.
+    #[cfg(target_os = "wasi")]
+    fn set_linger(&self, _dur: Option<Duration>) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(target_os = "wasi")]
+    fn linger(&self) -> io::Result<Option<Duration>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn join_multicast_v4(&self, _multiaddr: &Ipv4Addr, _interface: &Ipv4Addr) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn leave_multicast_v4(&self, _multiaddr: &Ipv4Addr, _interface: &Ipv4Addr) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn join_multicast_v6(&self, _multiaddr: &Ipv6Addr, _interface: u32) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn leave_multicast_v6(&self, _multiaddr: &Ipv6Addr, _interface: u32) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn set_multicast_loop_v4(&self, _on: bool) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn multicast_loop_v4(&self) -> io::Result<bool> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn set_multicast_loop_v6(&self, _on: bool) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn multicast_loop_v6(&self) -> io::Result<bool> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn set_multicast_ttl_v4(&self, _ttl: u32) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn multicast_ttl_v4(&self) -> io::Result<u32> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn set_multicast_hops_v6(&self, _hops: u32) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn multicast_hops_v6(&self) -> io::Result<u32> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn set_nodelay(&self, _nodelay: bool) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn nodelay(&self) -> io::Result<bool> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn set_reuse_address(&self, _reuse: bool) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn reuse_address(&self) -> io::Result<bool> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn set_ttl(&self, _ttl: u32) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn ttl(&self) -> io::Result<u32> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn set_broadcast(&self, _broadcast: bool) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn broadcast(&self) -> io::Result<bool> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(not(target_os = "wasi"))]
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        do_recv(self.as_sock(), buf, MSG_PEEK)
+    }
+
+    #[cfg(target_os = "wasi")]
+    fn peek(&self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn recv_oob(&self, buf: &mut [u8]) -> io::Result<usize> {
+        do_recv(self.as_sock(), buf, MSG_OOB)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn set_out_of_band_inline(&self, oobinline: bool) -> io::Result<()> {
+        set_opt(self.as_sock(), SOL_SOCKET, SO_OOBINLINE, oobinline as c_int)
+    }
+
+    #[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+    fn out_of_band_inline(&self) -> io::Result<bool> {
+        get_opt::<c_int>(self.as_sock(), SOL_SOCKET, SO_OOBINLINE).map(|v| v != 0)
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn recv_oob(&self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn set_out_of_band_inline(&self, _oobinline: bool) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(any(target_os = "wasi", target_os = "redox", target_os = "hermit"))]
+    fn out_of_band_inline(&self) -> io::Result<bool> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+fn ipv4addr2in_addr(addr: &Ipv4Addr) -> in_addr {
+    in_addr {
+        s_addr: u32::from_ne_bytes(addr.octets()),
+    }
+}
+
+#[cfg(not(any(target_os = "wasi", target_os = "redox", target_os = "hermit")))]
+fn ipv6addr2in6_addr(addr: &Ipv6Addr) -> in6_addr {
+    in6_addr {
+        s6_addr: addr.octets(),
+    }
 }
 
 #[cfg(windows)]
@@ -255,6 +905,7 @@ fn timeout2ms(dur: DWORD) -> Option<u32> {
     }
 }
 
+#[cfg(not(target_os = "wasi"))]
 fn linger2dur(linger_opt: linger) -> Option<Duration> {
     if linger_opt.l_onoff == 0 {
         None
@@ -264,30 +915,30 @@ fn linger2dur(linger_opt: linger) -> Option<Duration> {
 }
 
 #[cfg(windows)]
-fn dur2linger(dur: Option<Duration>) -> linger {
+fn dur2linger(dur: Option<Duration>) -> io::Result<linger> {
     match dur {
-        Some(d) => linger {
+        Some(d) => Ok(linger {
             l_onoff: 1,
-            l_linger: d.as_secs() as u16,
-        },
-        None => linger {
+            l_linger: checked_cast(d.as_secs())?,
+        }),
+        None => Ok(linger {
             l_onoff: 0,
             l_linger: 0,
-        },
+        }),
     }
 }
 
-#[cfg(any(unix, target_os = "redox"))]
-fn dur2linger(dur: Option<Duration>) -> linger {
+#[cfg(any(unix, target_os = "redox", target_os = "hermit"))]
+fn dur2linger(dur: Option<Duration>) -> io::Result<linger> {
     match dur {
-        Some(d) => linger {
+        Some(d) => Ok(linger {
             l_onoff: 1,
-            l_linger: d.as_secs() as c_int,
-        },
-        None => linger {
+            l_linger: checked_cast(d.as_secs())?,
+        }),
+        None => Ok(linger {
             l_onoff: 0,
             l_linger: 0,
-        },
+        }),
     }
 }
 
@@ -295,13 +946,17 @@ fn ms2dur(ms: u32) -> Duration {
     Duration::new((ms as u64) / 1000, (ms as u32) % 1000 * 1_000_000)
 }
 
-fn dur2ms(dur: Duration) -> u32 {
-    (dur.as_secs() as u32 * 1000) + (dur.subsec_nanos() / 1_000_000)
+fn dur2ms(dur: Duration) -> io::Result<u32> {
+    let secs_ms: u32 = checked_cast::<_, u32>(dur.as_secs())?
+        .checked_mul(1000)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "value out of range"))?;
+    secs_ms
+        .checked_add(dur.subsec_nanos() / 1_000_000)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "value out of range"))
 }
 
-fn int2usize(n: c_int) -> usize {
-    // TODO: casting c_int to a usize should be a checked cast
-    n as usize
+fn int2usize(n: c_int) -> io::Result<usize> {
+    checked_cast(n)
 }
 
 fn cvt<T: utils::One + PartialEq + Neg<Output = T>>(t: T) -> io::Result<T> {
@@ -321,3 +976,52 @@ fn cvt_win<T: PartialEq + utils::Zero>(t: T) -> io::Result<T> {
         Ok(t)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_cast_rejects_out_of_range_values() {
+        let err = checked_cast::<_, u8>(1_000usize).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn checked_cast_accepts_in_range_values() {
+        let n: u8 = checked_cast(200usize).unwrap();
+        assert_eq!(n, 200);
+    }
+
+    #[test]
+    fn dur2ms_round_trips_through_ms2dur() {
+        let ms = dur2ms(Duration::from_millis(1_500)).unwrap();
+        assert_eq!(ms, 1_500);
+        assert_eq!(ms2dur(ms), Duration::from_millis(1_500));
+    }
+
+    #[test]
+    fn dur2ms_rejects_durations_that_overflow_u32_millis() {
+        let err = dur2ms(Duration::from_secs(u64::from(u32::MAX))).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn tcp_keepalive_builder_tracks_each_field_independently() {
+        let ka = TcpKeepalive::new()
+            .with_idle(Duration::from_secs(30))
+            .with_interval(Duration::from_secs(5))
+            .with_retries(4);
+        assert_eq!(ka.idle, Some(Duration::from_secs(30)));
+        assert_eq!(ka.interval, Some(Duration::from_secs(5)));
+        assert_eq!(ka.retries, Some(4));
+    }
+
+    #[test]
+    fn tcp_keepalive_default_leaves_every_field_unset() {
+        let ka = TcpKeepalive::new();
+        assert_eq!(ka.idle, None);
+        assert_eq!(ka.interval, None);
+        assert_eq!(ka.retries, None);
+    }
+}